@@ -0,0 +1,259 @@
+use std::{
+    fs::File,
+    io::{Read, Write},
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use anyhow::{bail, Context};
+use zip::{write::FileOptions, CompressionMethod, ZipWriter};
+
+use crate::meta::Target;
+
+/// Inputs for assembling an APK from already-built `.so` trees.
+pub(crate) struct ApkConfig<'a> {
+    pub(crate) ndk_home: &'a Path,
+    pub(crate) platform: u8,
+    pub(crate) package_name: String,
+    /// The targets that were built, in the order they should be bundled.
+    pub(crate) targets: &'a [Target],
+    /// Directory holding one `<abi>/` sub-dir per target with the built `.so`
+    /// files (the `--output-dir` / jniLibs tree).
+    pub(crate) lib_src_dir: &'a Path,
+    /// Optional user-supplied `AndroidManifest.xml` template.
+    pub(crate) manifest_template: Option<&'a Path>,
+}
+
+/// Locate the Android SDK root from the usual environment variables.
+fn sdk_home() -> anyhow::Result<PathBuf> {
+    for var in ["ANDROID_SDK_ROOT", "ANDROID_HOME", "ANDROID_SDK_HOME"] {
+        if let Some(path) = std::env::var_os(var) {
+            let path = PathBuf::from(path);
+            if path.exists() {
+                return Ok(path);
+            }
+        }
+    }
+    bail!("Could not locate the Android SDK; set ANDROID_SDK_ROOT")
+}
+
+/// Find the newest `build-tools/<version>` directory in the SDK.
+fn build_tools_dir(sdk: &Path) -> anyhow::Result<PathBuf> {
+    let root = sdk.join("build-tools");
+    let mut versions = std::fs::read_dir(&root)
+        .with_context(|| format!("no build-tools under {}", root.display()))?
+        .flatten()
+        .map(|e| e.path())
+        .filter_map(|p| {
+            p.file_name()
+                .and_then(|n| n.to_str())
+                .and_then(|n| semver::Version::parse(n).ok())
+                .map(|v| (v, p))
+        })
+        .collect::<Vec<_>>();
+    versions.sort_by(|a, b| a.0.cmp(&b.0));
+    versions
+        .pop()
+        .map(|(_, p)| p)
+        .context("no usable build-tools version found")
+}
+
+#[cfg(target_os = "windows")]
+const EXE: &str = ".exe";
+#[cfg(not(target_os = "windows"))]
+const EXE: &str = "";
+
+fn tool(build_tools: &Path, name: &str) -> PathBuf {
+    build_tools.join(format!("{}{}", name, EXE))
+}
+
+/// Render the `AndroidManifest.xml`, either from the user's template (with
+/// `{package}`/`{platform}` substituted) or a minimal `NativeActivity` default.
+fn render_manifest(config: &ApkConfig) -> anyhow::Result<String> {
+    let template = match config.manifest_template {
+        Some(path) => std::fs::read_to_string(path)
+            .with_context(|| format!("reading manifest template {}", path.display()))?,
+        None => DEFAULT_MANIFEST.to_string(),
+    };
+
+    Ok(template
+        .replace("{package}", &config.package_name)
+        .replace("{platform}", &config.platform.to_string()))
+}
+
+const DEFAULT_MANIFEST: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<manifest xmlns:android="http://schemas.android.com/apk/res/android"
+    package="{package}">
+    <uses-sdk android:minSdkVersion="{platform}" android:targetSdkVersion="{platform}" />
+    <application android:hasCode="false" android:label="{package}">
+        <activity android:name="android.app.NativeActivity" android:exported="true">
+            <intent-filter>
+                <action android:name="android.intent.action.MAIN" />
+                <category android:name="android.intent.category.LAUNCHER" />
+            </intent-filter>
+        </activity>
+    </application>
+</manifest>
+"#;
+
+/// Assemble, align and sign an APK from the built ABI `.so` trees, writing it
+/// to `out_apk`. Mirrors the layout cargo-apk's `ApkBuilder` produces.
+pub(crate) fn build(config: &ApkConfig, out_apk: &Path) -> anyhow::Result<()> {
+    let sdk = sdk_home()?;
+    let build_tools = build_tools_dir(&sdk)?;
+    let android_jar = sdk
+        .join("platforms")
+        .join(format!("android-{}", config.platform))
+        .join("android.jar");
+
+    let staging = out_apk.with_extension("staging");
+    let _ = std::fs::remove_dir_all(&staging);
+    std::fs::create_dir_all(&staging)?;
+
+    // lib/<abi>/*.so, pulling in transitive runtime deps (libc++_shared.so).
+    for target in config.targets {
+        let src = config.lib_src_dir.join(target.android_abi());
+        if !src.exists() {
+            continue;
+        }
+        let dest = staging.join("lib").join(target.android_abi());
+        std::fs::create_dir_all(&dest)?;
+        for entry in std::fs::read_dir(&src)?.flatten() {
+            let path = entry.path();
+            if path.extension().and_then(|e| e.to_str()) == Some("so") {
+                std::fs::copy(&path, dest.join(path.file_name().unwrap()))?;
+            }
+        }
+        crate::cargo::resolve_needed_libs(config.ndk_home, *target, config.platform, &dest, &[])?;
+    }
+
+    let manifest_path = staging.join("AndroidManifest.xml");
+    std::fs::write(&manifest_path, render_manifest(config)?)?;
+
+    let unaligned = out_apk.with_extension("unaligned.apk");
+
+    // Link the manifest into a base APK.
+    run_tool(
+        tool(&build_tools, "aapt2"),
+        &[
+            "link".as_ref(),
+            "-I".as_ref(),
+            android_jar.as_os_str(),
+            "--manifest".as_ref(),
+            manifest_path.as_os_str(),
+            "--min-sdk-version".as_ref(),
+            config.platform.to_string().as_ref(),
+            "--target-sdk-version".as_ref(),
+            config.platform.to_string().as_ref(),
+            "-o".as_ref(),
+            unaligned.as_os_str(),
+        ],
+    )?;
+
+    // Add the native libraries (aapt2 link won't carry arbitrary files). `aapt`
+    // (v1) is gone from recent build-tools, so append them directly to the
+    // APK zip ourselves, uncompressed as Android expects shared libraries.
+    add_native_libs(&unaligned, &staging, config.targets)?;
+
+    // Align, then sign with the debug keystore. `-p` additionally page-aligns
+    // uncompressed `.so` entries, which is required for them to be mmap'd
+    // directly out of the APK (`extractNativeLibs=false`).
+    run_tool(
+        tool(&build_tools, "zipalign"),
+        &[
+            "-f".as_ref(),
+            "-p".as_ref(),
+            "4".as_ref(),
+            unaligned.as_os_str(),
+            out_apk.as_os_str(),
+        ],
+    )?;
+
+    sign_debug(&build_tools, out_apk)?;
+
+    let _ = std::fs::remove_file(&unaligned);
+    Ok(())
+}
+
+/// Append every built `lib/<abi>/*.so` under `staging` to `apk` as uncompressed
+/// zip entries, stored rather than deflated as Android requires for native
+/// libraries that are mapped directly out of the APK.
+fn add_native_libs(apk: &Path, staging: &Path, targets: &[Target]) -> anyhow::Result<()> {
+    let file = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(apk)
+        .with_context(|| format!("opening {}", apk.display()))?;
+    let mut writer = ZipWriter::new_append(file)
+        .with_context(|| format!("reading zip central directory of {}", apk.display()))?;
+    let options = FileOptions::default().compression_method(CompressionMethod::Stored);
+
+    for target in targets {
+        let abi_dir = staging.join("lib").join(target.android_abi());
+        if !abi_dir.exists() {
+            continue;
+        }
+        for entry in std::fs::read_dir(&abi_dir)?.flatten() {
+            let path = entry.path();
+            let rel = format!(
+                "lib/{}/{}",
+                target.android_abi(),
+                entry.file_name().to_string_lossy()
+            );
+            let mut contents = Vec::new();
+            File::open(&path)
+                .with_context(|| format!("reading {}", path.display()))?
+                .read_to_end(&mut contents)?;
+
+            writer.start_file(&rel, options)?;
+            writer.write_all(&contents)?;
+        }
+    }
+
+    writer.finish()?;
+    Ok(())
+}
+
+/// Sign `apk` in place with the standard Android debug keystore.
+fn sign_debug(build_tools: &Path, apk: &Path) -> anyhow::Result<()> {
+    let keystore = dirs::home_dir()
+        .context("could not resolve home directory")?
+        .join(".android")
+        .join("debug.keystore");
+
+    if !keystore.exists() {
+        log::warn!(
+            "Debug keystore {} not found; leaving APK unsigned",
+            keystore.display()
+        );
+        return Ok(());
+    }
+
+    run_tool(
+        tool(build_tools, "apksigner"),
+        &[
+            "sign".as_ref(),
+            "--ks".as_ref(),
+            keystore.as_os_str(),
+            "--ks-pass".as_ref(),
+            "pass:android".as_ref(),
+            apk.as_os_str(),
+        ],
+    )
+}
+
+fn run_tool(tool: PathBuf, args: &[&std::ffi::OsStr]) -> anyhow::Result<()> {
+    run_tool_in(tool, &std::env::current_dir()?, args)
+}
+
+fn run_tool_in(tool: PathBuf, dir: &Path, args: &[&std::ffi::OsStr]) -> anyhow::Result<()> {
+    let status = Command::new(&tool)
+        .current_dir(dir)
+        .args(args)
+        .status()
+        .with_context(|| format!("failed to invoke {}", tool.display()))?;
+    if !status.success() {
+        bail!("{} exited with {}", tool.display(), status);
+    }
+    Ok(())
+}
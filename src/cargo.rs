@@ -0,0 +1,232 @@
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    process::{Command, ExitStatus},
+};
+
+use crate::meta::Target;
+
+#[cfg(target_os = "linux")]
+const HOST_TAG: &str = "linux-x86_64";
+#[cfg(target_os = "macos")]
+const HOST_TAG: &str = "darwin-x86_64";
+#[cfg(target_os = "windows")]
+const HOST_TAG: &str = "windows-x86_64";
+
+#[cfg(target_os = "windows")]
+const EXE: &str = ".exe";
+#[cfg(not(target_os = "windows"))]
+const EXE: &str = "";
+
+/// OS libraries that ship with every Android device and must never be bundled
+/// into the APK; they are provided by the platform at runtime.
+const SYSTEM_LIBS: &[&str] = &[
+    "libc.so",
+    "libm.so",
+    "libdl.so",
+    "liblog.so",
+    "libandroid.so",
+    "libz.so",
+    "libGLESv1_CM.so",
+    "libGLESv2.so",
+    "libGLESv3.so",
+    "libEGL.so",
+    "libOpenSLES.so",
+    "libOpenMAXAL.so",
+    "libvulkan.so",
+    "libjnigraphics.so",
+    "libaaudio.so",
+    "libmediandk.so",
+    "libnativewindow.so",
+];
+
+/// The `toolchains/llvm/prebuilt/<host>` directory inside the NDK.
+fn toolchain_dir(ndk_home: &Path) -> PathBuf {
+    ndk_home
+        .join("toolchains")
+        .join("llvm")
+        .join("prebuilt")
+        .join(HOST_TAG)
+}
+
+/// The NDK sysroot library directory for `target` at the given `platform`.
+fn sysroot_lib_dir(ndk_home: &Path, target: Target, platform: u8) -> PathBuf {
+    toolchain_dir(ndk_home)
+        .join("sysroot")
+        .join("usr")
+        .join("lib")
+        .join(target.ndk_sysroot_triple())
+        .join(platform.to_string())
+}
+
+/// Invoke cargo for a single Android `target`, wiring up the linker so the
+/// final link resolves against the NDK.
+pub(crate) fn run(
+    dir: &Path,
+    ndk_home: &Path,
+    triple: &str,
+    platform: u8,
+    cargo_args: &[String],
+) -> ExitStatus {
+    let target: Target = triple.parse().expect("unsupported target triple");
+    let toolchain = toolchain_dir(ndk_home);
+    let bin_dir = toolchain.join("bin");
+
+    let clang = bin_dir.join(format!("clang{}", EXE));
+    let clangpp = bin_dir.join(format!("clang++{}", EXE));
+    let ar = bin_dir.join(format!("llvm-ar{}", EXE));
+
+    // Modern NDKs (r19+) drive clang directly with an API-versioned
+    // `--target`, rather than the deprecated per-API wrapper scripts (removed
+    // entirely in r23+).
+    let clang_target = format!("{}{}", target.llvm_triple(), platform);
+    let sysroot = toolchain.join("sysroot");
+    let flags = format!("--target={} --sysroot={}", clang_target, sysroot.display());
+
+    // The linker env var only accepts an executable path, so point it at
+    // plain clang (same binary as `CC_*`) and feed the versioned `--target`
+    // and `--sysroot` through per-target rustflags link-args instead.
+    let linker = &clang;
+
+    let triple_env = triple.replace('-', "_").to_uppercase();
+    let linker_var = format!("CARGO_TARGET_{}_LINKER", triple_env);
+
+    // `CARGO_ENCODED_RUSTFLAGS` takes precedence over everything else cargo
+    // reads rustflags from (a plain `RUSTFLAGS` env var, `CARGO_TARGET_*_RUSTFLAGS`,
+    // and `.cargo/config.toml`'s `[target.*] rustflags`), so setting it here
+    // would otherwise silently stomp whatever the user already has configured.
+    // Fold our link-args in on top of an existing `CARGO_ENCODED_RUSTFLAGS`,
+    // or failing that `RUSTFLAGS`, instead of clobbering it.
+    // `CARGO_TARGET_*_RUSTFLAGS` and config-file rustflags are not read, so
+    // they no longer apply to this build.
+    let mut rustflags: Vec<String> = match std::env::var("CARGO_ENCODED_RUSTFLAGS") {
+        Ok(encoded) if !encoded.is_empty() => encoded.split('\x1f').map(str::to_string).collect(),
+        _ => match std::env::var("RUSTFLAGS") {
+            Ok(plain) if !plain.is_empty() => plain.split(' ').map(str::to_string).collect(),
+            _ => Vec::new(),
+        },
+    };
+    rustflags.push(format!("-Clink-arg=--target={}", clang_target));
+    rustflags.push(format!("-Clink-arg=--sysroot={}", sysroot.display()));
+    let rustflags = rustflags.join("\x1f");
+
+    log::debug!("Toolchain for {}: target={}", triple, clang_target);
+
+    // cc-rs and common build scripts key their cross-compilers off the target
+    // triple; point them at the NDK's clang with the versioned target so
+    // vendored C/C++ dependencies cross-compile correctly.
+    Command::new("cargo")
+        .current_dir(dir)
+        .env(format!("CC_{}", triple), &clang)
+        .env(format!("CXX_{}", triple), &clangpp)
+        .env(format!("AR_{}", triple), &ar)
+        .env(format!("CFLAGS_{}", triple), &flags)
+        .env(format!("CXXFLAGS_{}", triple), &flags)
+        .env(linker_var, linker)
+        .env("CARGO_ENCODED_RUSTFLAGS", &rustflags)
+        .arg("build")
+        .arg("--target")
+        .arg(triple)
+        .args(cargo_args)
+        .status()
+        .expect("failed to invoke cargo")
+}
+
+/// Strip debug symbols from a built shared library in place.
+pub(crate) fn strip(ndk_home: &Path, _triple: &str, lib: &Path) -> ExitStatus {
+    let strip = toolchain_dir(ndk_home).join("bin").join("llvm-strip");
+
+    Command::new(strip)
+        .arg("--strip-unneeded")
+        .arg(lib)
+        .status()
+        .expect("failed to invoke llvm-strip")
+}
+
+/// Collect the `DT_NEEDED` entries of an ELF shared object.
+fn needed_libs(lib: &Path) -> std::io::Result<Vec<String>> {
+    let bytes = std::fs::read(lib)?;
+    match goblin::elf::Elf::parse(&bytes) {
+        Ok(elf) => Ok(elf.libraries.iter().map(|s| s.to_string()).collect()),
+        Err(e) => {
+            log::warn!("Could not parse {} as ELF: {}", lib.display(), e);
+            Ok(Vec::new())
+        }
+    }
+}
+
+/// Recursively resolve the transitive runtime dependencies of every shared
+/// library already present in `abi_dir`, copying each resolved library next to
+/// them until a fixed point is reached.
+///
+/// Libraries are resolved against the NDK sysroot for `target` plus any extra
+/// `search_paths` (e.g. `-L` directories). OS-provided libraries are skipped,
+/// and resolution is deduplicated by file name.
+pub(crate) fn resolve_needed_libs(
+    ndk_home: &Path,
+    target: Target,
+    platform: u8,
+    abi_dir: &Path,
+    search_paths: &[PathBuf],
+) -> std::io::Result<()> {
+    let mut lib_dirs = vec![sysroot_lib_dir(ndk_home, target, platform)];
+    // The versionless sysroot dir holds the stub libraries that aren't
+    // API-specific (e.g. libc++_shared.so).
+    lib_dirs.push(
+        toolchain_dir(ndk_home)
+            .join("sysroot")
+            .join("usr")
+            .join("lib")
+            .join(target.ndk_sysroot_triple()),
+    );
+    lib_dirs.extend_from_slice(search_paths);
+
+    // Seed the work list with everything the build already produced, and track
+    // resolved names so we only copy each library once.
+    let mut resolved: HashSet<String> = HashSet::new();
+    let mut queue: Vec<PathBuf> = std::fs::read_dir(abi_dir)?
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.extension().and_then(|e| e.to_str()) == Some("so"))
+        .collect();
+
+    for p in &queue {
+        if let Some(name) = p.file_name().and_then(|n| n.to_str()) {
+            resolved.insert(name.to_string());
+        }
+    }
+
+    while let Some(lib) = queue.pop() {
+        for needed in needed_libs(&lib)? {
+            if resolved.contains(&needed) || SYSTEM_LIBS.contains(&needed.as_str()) {
+                continue;
+            }
+
+            let found = lib_dirs
+                .iter()
+                .map(|d| d.join(&needed))
+                .find(|p| p.exists());
+
+            match found {
+                Some(src) => {
+                    let dest = abi_dir.join(&needed);
+                    log::info!("Bundling {} -> {}", src.display(), dest.display());
+                    std::fs::copy(&src, &dest)?;
+                    resolved.insert(needed);
+                    // Recurse into the newly copied library.
+                    queue.push(dest);
+                }
+                None => {
+                    log::warn!(
+                        "Could not resolve dependency `{}` needed by {}",
+                        needed,
+                        lib.display()
+                    );
+                    resolved.insert(needed);
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -1,4 +1,8 @@
-use std::{env, ffi::OsStr, path::PathBuf};
+use std::{
+    env,
+    ffi::OsStr,
+    path::{Path, PathBuf},
+};
 
 use cargo_metadata::MetadataCommand;
 use gumdrop::Options;
@@ -21,41 +25,109 @@ struct Args {
 
     #[options(help = "output to a jniLibs directory in the correct sub-directories")]
     output_dir: Option<PathBuf>,
+
+    #[options(help = "semver requirement for the NDK version (an NDK set via environment is only warned about)")]
+    ndk_version: Option<String>,
+
+    #[options(help = "after building, package the libraries into an APK at this path")]
+    package_apk: Option<PathBuf>,
+
+    #[options(help = "custom AndroidManifest.xml template to use when packaging an APK")]
+    manifest_template: Option<PathBuf>,
+}
+
+/// Read the `Pkg.Revision` out of an NDK installation's `source.properties`
+/// and parse it into a semver version.
+fn ndk_revision(path: &Path) -> Option<semver::Version> {
+    let props = std::fs::read_to_string(path.join("source.properties")).ok()?;
+    for line in props.lines() {
+        if let Some(rest) = line.strip_prefix("Pkg.Revision") {
+            let value = rest.trim_start_matches(['=', ' ', '\t']).trim();
+            return semver::Version::parse(value).ok();
+        }
+    }
+    None
 }
 
-fn derive_ndk_path() -> Option<PathBuf> {
+/// Warn if an explicitly-configured NDK path doesn't satisfy the requested
+/// version requirement; we still honour the override.
+fn warn_on_mismatch(path: &Path, req: Option<&semver::VersionReq>) {
+    if let Some(req) = req {
+        match ndk_revision(path) {
+            Some(v) if !req.matches(&v) => {
+                log::warn!(
+                    "NDK at {} is version {}, which does not satisfy `{}`",
+                    path.display(),
+                    v,
+                    req
+                );
+            }
+            _ => {}
+        }
+    }
+}
+
+fn derive_ndk_path(req: Option<&semver::VersionReq>) -> Option<PathBuf> {
     if let Some(path) = env::var_os("ANDROID_NDK_HOME") {
-        return Some(PathBuf::from(path));
+        let path = PathBuf::from(path);
+        warn_on_mismatch(&path, req);
+        return Some(path);
     };
 
     if let Some(path) = env::var_os("NDK_HOME") {
-        return Some(PathBuf::from(path));
+        let path = PathBuf::from(path);
+        warn_on_mismatch(&path, req);
+        return Some(path);
     };
 
     if let Some(sdk_path) = env::var_os("ANDROID_SDK_HOME") {
         let path = PathBuf::from(sdk_path).join("ndk-bundle");
 
         if path.exists() {
+            warn_on_mismatch(&path, req);
             return Some(path);
         }
     };
 
     // Check Android Studio installed directories
     #[cfg(windows)]
-    let base_dir = pathos::user::local_dir();
+    let base_dir = pathos::user::local_dir().ok()?;
     #[cfg(any(target_os = "macos", target_os = "linux"))]
-    let base_dir = pathos::user::data_dir();
+    let base_dir = pathos::user::data_dir().ok()?;
 
     let ndk_dir = base_dir.join("Android").join("sdk").join("ndk");
     if ndk_dir.exists() {
-        let mut paths = std::fs::read_dir(&ndk_dir)
+        // Each sub-directory is a side-by-side NDK install; select by its
+        // parsed `Pkg.Revision` rather than the lexical directory name.
+        let mut candidates = std::fs::read_dir(&ndk_dir)
             .ok()?
             .flat_map(Result::ok)
             .map(|x| x.path())
+            .filter_map(|p| ndk_revision(&p).map(|v| (v, p)))
             .collect::<Vec<_>>();
-        paths.sort();
-        paths.reverse();
-        return paths.first().cloned();
+        candidates.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let selected = candidates
+            .iter()
+            .rfind(|(v, _)| req.is_none_or(|r| r.matches(v)));
+
+        match selected {
+            Some((_, path)) => return Some(path.clone()),
+            None if req.is_some() => {
+                let found = candidates
+                    .iter()
+                    .map(|(v, _)| v.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                log::error!(
+                    "No installed NDK satisfies `{}`. Versions found: {}",
+                    req.unwrap(),
+                    if found.is_empty() { "none" } else { &found }
+                );
+                return None;
+            }
+            None => {}
+        }
     }
 
     None
@@ -95,9 +167,30 @@ pub(crate) fn run(args: Vec<String>) {
         .exec()
         .unwrap();
 
+    let current_dir = std::env::current_dir().expect("current directory could not be resolved");
+    let config = match crate::meta::config(&current_dir.join("Cargo.toml"), is_release) {
+        Ok(v) => v,
+        Err(e) => {
+            log::error!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    // A requirement on the command line overrides the manifest's.
+    let ndk_version = match args.ndk_version.as_ref() {
+        Some(req) => match semver::VersionReq::parse(req) {
+            Ok(v) => Some(v),
+            Err(e) => {
+                log::error!("Invalid --ndk-version `{}`: {}", req, e);
+                std::process::exit(2);
+            }
+        },
+        None => config.ndk_version.clone(),
+    };
+
     // We used to check for NDK_HOME, so we'll keep doing that. But we'll also try ANDROID_NDK_HOME
     // and $ANDROID_SDK_HOME/ndk-bundle as this is how Android Studio configures the world
-    let ndk_home = match derive_ndk_path() {
+    let ndk_home = match derive_ndk_path(ndk_version.as_ref()) {
         Some(v) => {
             log::info!("Using NDK at path: {}", v.display());
             v
@@ -111,15 +204,6 @@ pub(crate) fn run(args: Vec<String>) {
         }
     };
 
-    let current_dir = std::env::current_dir().expect("current directory could not be resolved");
-    let config = match crate::meta::config(&current_dir.join("Cargo.toml"), is_release) {
-        Ok(v) => v,
-        Err(e) => {
-            log::error!("{}", e);
-            std::process::exit(1);
-        }
-    };
-
     // Try command line, then config. Config falls back to defaults in any case.
     let targets = if !args.target.is_empty() {
         args.target
@@ -128,7 +212,7 @@ pub(crate) fn run(args: Vec<String>) {
     };
 
     let platform = config.platform;
-    let platform = args.platform.unwrap_or_else(|| platform);
+    let platform = args.platform.unwrap_or(platform);
 
     if let Some(output_dir) = args.output_dir.as_ref() {
         std::fs::create_dir_all(output_dir).expect("failed to create output directory");
@@ -159,13 +243,24 @@ pub(crate) fn run(args: Vec<String>) {
         }
     }
 
-    let out_dir = metadata.target_directory;
+    let out_dir = metadata.target_directory.clone();
 
-    if let Some(output_dir) = args.output_dir.as_ref() {
+    // Packaging an APK needs the jniLibs tree too, so fall back to a staging
+    // directory under the target dir when no explicit --output-dir was given.
+    let lib_dir = args.output_dir.clone().or_else(|| {
+        args.package_apk.as_ref().map(|_| {
+            out_dir
+                .join("cargo-ndk")
+                .join("jniLibs")
+                .into_std_path_buf()
+        })
+    });
+
+    if let Some(output_dir) = lib_dir.as_ref() {
         log::info!("Copying libraries to {}...", &output_dir.display());
 
-        for target in targets {
-            let arch_output_dir = output_dir.join(target.to_string());
+        for target in targets.iter() {
+            let arch_output_dir = output_dir.join(target.android_abi());
             std::fs::create_dir_all(&arch_output_dir).unwrap();
 
             let dir =
@@ -173,7 +268,7 @@ pub(crate) fn run(args: Vec<String>) {
                     .join(target.triple())
                     .join(if is_release { "release" } else { "debug" });
 
-            log::trace!("Target path: {}", dir.display());
+            log::trace!("Target path: {}", dir);
 
             let so_files = std::fs::read_dir(&dir)
                 .ok()
@@ -188,8 +283,42 @@ pub(crate) fn run(args: Vec<String>) {
                 log::info!("{} -> {}", &so_file.display(), dest.display());
                 std::fs::copy(so_file, &dest).unwrap();
 
-                let _ = crate::cargo::strip(&ndk_home, &target.triple(), &dest);
+                let _ = crate::cargo::strip(&ndk_home, target.triple(), &dest);
             }
+
+            // Pull in transitive runtime dependencies (libc++_shared.so etc.)
+            // so `System.loadLibrary` resolves at runtime.
+            if let Err(e) = crate::cargo::resolve_needed_libs(
+                &ndk_home,
+                *target,
+                platform,
+                &arch_output_dir,
+                &[],
+            ) {
+                log::warn!("Failed to bundle runtime dependencies: {}", e);
+            }
+        }
+    }
+
+    if let Some(apk_path) = args.package_apk.as_ref() {
+        let package_name = metadata
+            .root_package()
+            .map(|p| format!("com.example.{}", p.name.replace('-', "_")))
+            .unwrap_or_else(|| "com.example.app".to_string());
+
+        let apk_config = crate::apk::ApkConfig {
+            ndk_home: &ndk_home,
+            platform,
+            package_name,
+            targets: &targets,
+            lib_src_dir: lib_dir.as_ref().unwrap(),
+            manifest_template: args.manifest_template.as_deref(),
+        };
+
+        log::info!("Packaging APK at {}...", apk_path.display());
+        if let Err(e) = crate::apk::build(&apk_config, apk_path) {
+            log::error!("Failed to package APK: {}", e);
+            std::process::exit(1);
         }
     }
 }
@@ -0,0 +1,18 @@
+mod apk;
+mod cargo;
+mod cli;
+mod meta;
+
+fn main() {
+    env_logger::init();
+
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    // When invoked as `cargo ndk`, cargo passes the subcommand name as the
+    // first argument; drop it so our parser sees only the real arguments.
+    let args = match args.split_first() {
+        Some((first, rest)) if first == "ndk" => rest.to_vec(),
+        _ => args,
+    };
+
+    cli::run(args);
+}
@@ -0,0 +1,170 @@
+use std::{fmt, path::Path, str::FromStr};
+
+use serde::{de, Deserialize, Deserializer};
+
+/// A supported Android build target.
+///
+/// The string representation is always the Rust target triple, which is what
+/// `--target` and the `[package.metadata.ndk]` config accept. The Android
+/// jniLibs ABI name (`arm64-v8a`, …) is available separately via
+/// [`Target::android_abi`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum Target {
+    ArmeabiV7a,
+    Arm64V8a,
+    X86,
+    X86_64,
+}
+
+impl Target {
+    /// The Rust target triple for this ABI.
+    pub(crate) fn triple(self) -> &'static str {
+        match self {
+            Target::ArmeabiV7a => "armv7-linux-androideabi",
+            Target::Arm64V8a => "aarch64-linux-android",
+            Target::X86 => "i686-linux-android",
+            Target::X86_64 => "x86_64-linux-android",
+        }
+    }
+
+    /// The Android ABI name used for the `jniLibs/<abi>` directory layout.
+    ///
+    /// This differs from the Rust triple (e.g. `aarch64-linux-android` vs.
+    /// `arm64-v8a`); the APK packager only understands the ABI names.
+    pub(crate) fn android_abi(self) -> &'static str {
+        match self {
+            Target::ArmeabiV7a => "armeabi-v7a",
+            Target::Arm64V8a => "arm64-v8a",
+            Target::X86 => "x86",
+            Target::X86_64 => "x86_64",
+        }
+    }
+
+    /// The triple used to name the clang target (`--target=<this><platform>`)
+    /// and the per-API wrapper scripts. For `armv7` this is the `armv7a-`
+    /// spelling that clang expects rather than the Rust `armv7-` triple.
+    pub(crate) fn llvm_triple(self) -> &'static str {
+        match self {
+            Target::ArmeabiV7a => "armv7a-linux-androideabi",
+            Target::Arm64V8a => "aarch64-linux-android",
+            Target::X86 => "i686-linux-android",
+            Target::X86_64 => "x86_64-linux-android",
+        }
+    }
+
+    /// The triple used to name the NDK sysroot library directory
+    /// (`sysroot/usr/lib/<this>`). For `armv7` the sysroot uses the
+    /// `arm-linux-androideabi` spelling.
+    pub(crate) fn ndk_sysroot_triple(self) -> &'static str {
+        match self {
+            Target::ArmeabiV7a => "arm-linux-androideabi",
+            Target::Arm64V8a => "aarch64-linux-android",
+            Target::X86 => "i686-linux-android",
+            Target::X86_64 => "x86_64-linux-android",
+        }
+    }
+}
+
+impl FromStr for Target {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "armv7-linux-androideabi" | "armv7a-linux-androideabi" | "armeabi-v7a" => {
+                Target::ArmeabiV7a
+            }
+            "aarch64-linux-android" | "arm64-v8a" => Target::Arm64V8a,
+            "i686-linux-android" | "x86" => Target::X86,
+            "x86_64-linux-android" | "x86_64" => Target::X86_64,
+            _ => return Err(format!("unsupported target: {}", s)),
+        })
+    }
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.triple())
+    }
+}
+
+impl<'de> Deserialize<'de> for Target {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        Target::from_str(&s).map_err(de::Error::custom)
+    }
+}
+
+/// The default API level used when none is configured.
+const DEFAULT_PLATFORM: u8 = 21;
+
+/// Resolved build configuration, merged from `[package.metadata.ndk]` and the
+/// built-in defaults.
+#[derive(Debug)]
+pub(crate) struct Config {
+    pub(crate) targets: Vec<Target>,
+    pub(crate) platform: u8,
+    /// Optional NDK version constraint (semver requirement) from the manifest.
+    pub(crate) ndk_version: Option<semver::VersionReq>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct NdkMetadata {
+    #[serde(default)]
+    targets: Vec<Target>,
+    platform: Option<u8>,
+    ndk_version: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PackageMetadata {
+    ndk: Option<NdkMetadata>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Package {
+    metadata: Option<PackageMetadata>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Manifest {
+    package: Option<Package>,
+}
+
+/// Parse `[package.metadata.ndk]` from the manifest at `manifest_path`,
+/// falling back to sensible defaults for anything left unset.
+pub(crate) fn config(manifest_path: &Path, _is_release: bool) -> anyhow::Result<Config> {
+    let text = std::fs::read_to_string(manifest_path)?;
+    let manifest: Manifest = toml::from_str(&text)?;
+
+    let ndk = manifest
+        .package
+        .and_then(|p| p.metadata)
+        .and_then(|m| m.ndk)
+        .unwrap_or_default();
+
+    let targets = if ndk.targets.is_empty() {
+        vec![
+            Target::ArmeabiV7a,
+            Target::Arm64V8a,
+            Target::X86,
+            Target::X86_64,
+        ]
+    } else {
+        ndk.targets
+    };
+
+    let ndk_version = match ndk.ndk_version {
+        Some(req) => Some(semver::VersionReq::parse(&req)?),
+        None => None,
+    };
+
+    Ok(Config {
+        targets,
+        platform: ndk.platform.unwrap_or(DEFAULT_PLATFORM),
+        ndk_version,
+    })
+}